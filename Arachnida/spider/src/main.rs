@@ -1,11 +1,22 @@
 use clap::Parser;
+use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Mutex, Semaphore};
+
+/// Error type for the async pipeline. `tokio::spawn` requires futures
+/// whose output is `Send`, so `dyn Error` alone isn't enough here.
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
 /// spider - downloads images from websites
 #[derive(Parser)]
 struct Args {
-	/// The URL to scrape
-	url: String,
+	/// The URL to scrape (optional if --file is given)
+	url: Option<String>,
+
+	/// Read newline-separated seed URLs from a file
+	#[arg(short = 'f', long)]
+	file: Option<PathBuf>,
 
 	/// Recursive download images
 	#[arg(short = 'r', long)]
@@ -17,19 +28,121 @@ struct Args {
 
 	#[arg(short = 'p', long, default_value = "./data/")]
 	path: PathBuf,
+
+	/// Maximum number of concurrent requests
+	#[arg(short = 'j', long, default_value = "8")]
+	jobs: usize,
+
+	/// Resize downloaded images to fit within this width, preserving aspect ratio
+	#[arg(long)]
+	max_width: Option<u32>,
+
+	/// Resize downloaded images to fit within this height, preserving aspect ratio
+	#[arg(long)]
+	max_height: Option<u32>,
+
+	/// Number of retries for transient HTTP failures
+	#[arg(long, default_value = "5")]
+	retries: usize,
 }
 
-fn fetch_html(url: &str) -> Result<String, Box<dyn std::error::Error>> {
-	let client = reqwest::blocking::Client::builder()
+/// Builds the shared HTTP client: caps redirects at 10 hops and treats a
+/// redirect to a `/404` landing path (e.g. `/404`, `/404.html`) as a hard
+/// stop rather than following it.
+fn build_client() -> Result<reqwest::Client, BoxError> {
+	let redirect_policy = reqwest::redirect::Policy::custom(|attempt| {
+		if attempt.previous().len() > 10 {
+			attempt.error("too many redirects")
+		} else if attempt.url().path().starts_with("/404") {
+			attempt.stop()
+		} else {
+			attempt.follow()
+		}
+	});
+
+	Ok(reqwest::Client::builder()
 		.user_agent("Mozilla/5.0 (Spider/1.0)")
-		.build()?;
-	
-	let response = client.get(url).send()?;
-	let body = response.text()?;
+		.redirect(redirect_policy)
+		.build()?)
+}
+
+/// Retries `request` on connection errors, timeouts, and 5xx responses
+/// with exponential backoff, up to `retries` additional attempts. 4xx
+/// responses like a definitive 404 are returned immediately.
+///
+/// Holds a permit from `limit` for the whole call so every outbound
+/// request, whether it's a page fetch or an image download, counts
+/// against the same `--jobs` ceiling.
+async fn send_with_retries(
+	client: &reqwest::Client,
+	url: &str,
+	retries: usize,
+	limit: &Semaphore,
+) -> Result<reqwest::Response, BoxError> {
+	let _permit = limit.acquire().await?;
+	let mut attempt = 0;
+	loop {
+		match client.get(url).send().await {
+			Ok(response) if response.status().is_server_error() && attempt < retries => {
+				eprintln!("{}: server error {}, retrying ({}/{})", url, response.status(), attempt + 1, retries);
+			}
+			Ok(response) => return Ok(response.error_for_status()?),
+			Err(e) if (e.is_connect() || e.is_timeout()) && attempt < retries => {
+				eprintln!("{}: {}, retrying ({}/{})", url, e, attempt + 1, retries);
+			}
+			Err(e) => return Err(e.into()),
+		}
+
+		let backoff_ms = 200u64.saturating_mul(1u64 << attempt.min(10));
+		tokio::time::sleep(std::time::Duration::from_millis(backoff_ms)).await;
+		attempt += 1;
+	}
+}
+
+/// Bounds to shrink a saved image into, or none to leave it untouched.
+#[derive(Clone, Copy)]
+struct ResizeBounds {
+	max_width: Option<u32>,
+	max_height: Option<u32>,
+}
+
+impl ResizeBounds {
+	fn is_some(&self) -> bool {
+		self.max_width.is_some() || self.max_height.is_some()
+	}
+}
+
+/// Decodes the image at `path`, shrinks it to fit within `bounds` using a
+/// Lanczos filter (preserving aspect ratio), and overwrites it in place.
+fn resize_to_fit(path: &PathBuf, bounds: ResizeBounds) -> Result<(), BoxError> {
+	use image::imageops::FilterType;
+
+	let img = image::open(path)?;
+	let target_width = bounds.max_width.unwrap_or(img.width()).min(img.width());
+	let target_height = bounds.max_height.unwrap_or(img.height()).min(img.height());
+
+	let resized = img.resize(target_width, target_height, FilterType::Lanczos3);
+	resized.save(path)?;
+	Ok(())
+}
+
+/// Reads newline-separated seed URLs from a file, skipping blank lines.
+fn read_seed_urls(path: &PathBuf) -> Result<Vec<String>, BoxError> {
+	let contents = std::fs::read_to_string(path)?;
+	Ok(contents
+		.lines()
+		.map(|line| line.trim().to_string())
+		.filter(|line| !line.is_empty())
+		.collect())
+}
+
+async fn fetch_html(client: &reqwest::Client, url: &str, retries: usize, limit: &Semaphore) -> Result<String, BoxError> {
+	let response = send_with_retries(client, url, retries, limit).await?;
+	let body = response.text().await?;
 	Ok(body)
 }
 
-fn find_images(html: &str, base_url: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+fn find_images(html: &str, base_url: &str) -> Result<Vec<String>, BoxError> {
 	use scraper::{Html, Selector};
 	use url::Url;
 
@@ -41,12 +154,19 @@ fn find_images(html: &str, base_url: &str) -> Result<Vec<String>, Box<dyn std::e
 	for element in document.select(&img_selector) {
 		let src = element.value().attr("src")
 			.or_else(|| element.value().attr("data-src"));
-			
+
 		if let Some(src) = src {
-			if src.starts_with("data:") || src.is_empty() {
+			if src.is_empty() {
 				continue;
 			}
-			
+
+			// data: URIs carry the image inline; keep them as-is instead of
+			// resolving them against the page URL.
+			if src.starts_with("data:") {
+				img_urls.push(src.to_string());
+				continue;
+			}
+
 			match Url::parse(base_url)?.join(src) {
 				Ok(absolute_url) => img_urls.push(absolute_url.to_string()),
 				Err(e) => eprintln!("Failed to parse URL {}: {}", src, e),
@@ -56,42 +176,70 @@ fn find_images(html: &str, base_url: &str) -> Result<Vec<String>, Box<dyn std::e
 	Ok(img_urls)
 }
 
+/// Cheap pre-filter before the byte-sniffer: rejects URLs with a
+/// recognizable non-image extension (e.g. `.html`, `.pdf`), but passes
+/// through `data:` URIs and extensionless URLs (query-string endpoints,
+/// CDN URLs) so `sniff_image_type` in `download_image` gets the final say.
 fn is_valid_image(url: &str) -> bool {
-	let lower_url = url.to_lowercase();
-	
-	let valid_extensions = [".jpg", ".jpeg", ".png", ".gif", ".bmp"];
-	let has_valid_ext = valid_extensions.iter().any(|ext| {
-		lower_url.split('?').next().unwrap_or("").ends_with(ext)
-	});
-	
-	if lower_url.contains(".svg") {
-		return false;
+	if url.starts_with("data:") {
+		return true;
+	}
+
+	let path_only = url.split('?').next().unwrap_or("");
+	match std::path::Path::new(path_only).extension().and_then(|ext| ext.to_str()) {
+		Some(ext) => {
+			let valid_extensions = ["jpg", "jpeg", "png", "gif", "bmp", "webp"];
+			valid_extensions.contains(&ext.to_lowercase().as_str())
+		}
+		None => true,
 	}
-	
-	has_valid_ext
 }
 
-fn download_image(url: &str, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-	use std::fs;
-	use std::io::Write;
+/// Inspects the first bytes of a downloaded body against a magic-number
+/// table and returns the detected `image/*` media type, if any.
+fn sniff_image_type(bytes: &[u8]) -> Option<&'static str> {
+	if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+		Some("image/gif")
+	} else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+		Some("image/jpeg")
+	} else if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+		Some("image/png")
+	} else if bytes.starts_with(b"BM") {
+		Some("image/bmp")
+	} else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+		Some("image/webp")
+	} else if bytes.starts_with(b"<?xml") || bytes.starts_with(b"<svg") {
+		Some("image/svg+xml")
+	} else {
+		None
+	}
+}
 
-	fs::create_dir_all(path)?;
+/// Maps a detected `image/*` media type to the file extension it should
+/// be saved with.
+fn extension_for_image_type(media_type: &str) -> Option<&'static str> {
+	match media_type {
+		"image/gif" => Some("gif"),
+		"image/jpeg" => Some("jpg"),
+		"image/png" => Some("png"),
+		"image/bmp" => Some("bmp"),
+		"image/webp" => Some("webp"),
+		"image/svg+xml" => Some("svg"),
+		_ => None,
+	}
+}
 
-	let client = reqwest::blocking::Client::builder()
-		.user_agent("Mozilla/5.0 (Spider/1.0)")
-		.build()?;
-	
-	let response = client.get(url).send()?;
-	let bytes = response.bytes()?;
+/// Writes sniffed, already-validated image bytes to `path/<filename>`,
+/// truncating an overlong filename while preserving its extension, and
+/// optionally shrinking the result to fit `bounds`. Shared by network
+/// downloads and decoded `data:` URIs so both land through the same save
+/// path, and returns the final on-disk path.
+async fn save_image_bytes(bytes: &[u8], mut filename: String, path: &PathBuf, bounds: ResizeBounds) -> Result<PathBuf, BoxError> {
+	use tokio::fs;
+	use tokio::io::AsyncWriteExt;
+
+	fs::create_dir_all(path).await?;
 
-	let mut filename = url.split('/').last().unwrap_or("image").to_string();
-	
-	if let Some(pos) = filename.find('?') {
-		filename.truncate(pos);
-	}
-	
-	filename = urlencoding::decode(&filename)?.into_owned();
-	
 	const MAX_LEN: usize = 200;
 	if filename.len() > MAX_LEN {
 		if let Some(ext_pos) = filename.rfind('.') {
@@ -102,15 +250,106 @@ fn download_image(url: &str, path: &PathBuf) -> Result<(), Box<dyn std::error::E
 			filename.truncate(MAX_LEN);
 		}
 	}
-	
+
 	let filepath = path.join(&filename);
 
-	let mut file = fs::File::create(&filepath)?;
-	file.write_all(&bytes)?;
-	Ok(())
+	let mut file = fs::File::create(&filepath).await?;
+	file.write_all(bytes).await?;
+
+	if bounds.is_some() {
+		let resize_path = filepath.clone();
+		tokio::task::spawn_blocking(move || resize_to_fit(&resize_path, bounds)).await??;
+	}
+
+	Ok(filepath)
+}
+
+async fn download_image(client: &reqwest::Client, url: &str, path: &PathBuf, bounds: ResizeBounds, retries: usize, limit: &Semaphore) -> Result<PathBuf, BoxError> {
+	let response = send_with_retries(client, url, retries, limit).await?;
+	let bytes = response.bytes().await?;
+
+	let media_type = match sniff_image_type(&bytes) {
+		Some("image/svg+xml") => return Err(format!("{}: rejected, SVG image", url).into()),
+		Some(media_type) => media_type,
+		None => return Err(format!("{}: rejected, not a recognized image type", url).into()),
+	};
+
+	let mut filename = url.split('/').next_back().unwrap_or("image").to_string();
+
+	if let Some(pos) = filename.find('?') {
+		filename.truncate(pos);
+	}
+
+	filename = urlencoding::decode(&filename)?.into_owned();
+
+	if let Some(ext_pos) = filename.rfind('.') {
+		filename.truncate(ext_pos);
+	}
+	filename = format!("{}.{}", filename, extension_for_image_type(media_type).unwrap());
+
+	save_image_bytes(&bytes, filename, path, bounds).await
+}
+
+/// Parses a `data:[<mediatype>][;base64],<payload>` URI, decoding the
+/// payload as base64 or (if the `;base64` flag is absent) percent-encoded
+/// text, and returns the declared media type alongside the decoded bytes.
+fn decode_data_url(data_url: &str) -> Result<(String, Vec<u8>), BoxError> {
+	let rest = data_url.strip_prefix("data:").ok_or("not a data: URL")?;
+	let comma = rest.find(',').ok_or("malformed data: URL: missing comma")?;
+	let meta = &rest[..comma];
+	let payload = &rest[comma + 1..];
+
+	let (media_type, is_base64) = match meta.strip_suffix(";base64") {
+		Some(media_type) => (media_type, true),
+		None => (meta, false),
+	};
+	let media_type = if media_type.is_empty() { "text/plain" } else { media_type };
+
+	let bytes = if is_base64 {
+		use base64::Engine;
+		base64::engine::general_purpose::STANDARD.decode(payload)?
+	} else {
+		// Byte-level decode: a non-base64 data: URL can carry arbitrary binary
+		// data, which `urlencoding::decode` would reject as invalid UTF-8.
+		urlencoding::decode_binary(payload.as_bytes()).into_owned()
+	};
+
+	Ok((media_type.to_string(), bytes))
+}
+
+/// Saves an embedded `data:` image under a content-hash filename so that
+/// distinct inline images don't overwrite each other; a repeated image
+/// maps to the same file.
+async fn save_data_url_image(data_url: &str, path: &PathBuf, bounds: ResizeBounds) -> Result<PathBuf, BoxError> {
+	use sha2::{Digest, Sha256};
+
+	let (declared_type, bytes) = decode_data_url(data_url)?;
+
+	let media_type = match sniff_image_type(&bytes) {
+		Some("image/svg+xml") => return Err("rejected inline data: URL: SVG image".into()),
+		Some(media_type) => media_type,
+		None => return Err(format!("rejected inline data: URL: not a recognized image type (declared {})", declared_type).into()),
+	};
+	let ext = extension_for_image_type(media_type).unwrap();
+
+	let hash_hex = format!("{:x}", Sha256::digest(&bytes));
+	let filename = format!("{}.{}", &hash_hex[..16], ext);
+
+	save_image_bytes(&bytes, filename, path, bounds).await
 }
 
-fn find_links(html: &str, base_url: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+/// Saves one image source, dispatching to the `data:` URI decoder or the
+/// network downloader depending on the source's scheme. Returns the
+/// resulting on-disk path so callers can report `url -> path`.
+async fn save_image(client: &reqwest::Client, src: &str, path: &PathBuf, bounds: ResizeBounds, retries: usize, limit: &Semaphore) -> Result<PathBuf, BoxError> {
+	if src.starts_with("data:") {
+		save_data_url_image(src, path, bounds).await
+	} else {
+		download_image(client, src, path, bounds, retries, limit).await
+	}
+}
+
+fn find_links(html: &str, base_url: &str) -> Result<Vec<String>, BoxError> {
 	use scraper::{Html, Selector};
 	use url::Url;
 
@@ -132,67 +371,198 @@ fn find_links(html: &str, base_url: &str) -> Result<Vec<String>, Box<dyn std::er
 	Ok(links)
 }
 
-fn crawl_recursive(url: &str,
-	depth: usize,
+/// Per-crawl settings that stay constant across every `crawl_page` task,
+/// bundled together so the task spawn site doesn't need a long argument list.
+///
+/// `request_limit` is shared by every task (page fetches and image
+/// downloads alike) so the total number of requests in flight at once is
+/// bounded by `jobs`, not `jobs` pages each fanning out `jobs` downloads.
+#[derive(Clone)]
+struct CrawlConfig {
 	max_depth: usize,
-	visited: &mut std::collections::HashSet<String>,
-	save_path: &PathBuf,
-) -> Result<(), Box<dyn std::error::Error>> {
-	
-	if depth > max_depth || visited.contains(url) {
-		return Ok(());
-	}
-	
-	visited.insert(url.to_string());
+	jobs: usize,
+	bounds: ResizeBounds,
+	retries: usize,
+	request_limit: Arc<Semaphore>,
+}
+
+/// Fetches a single page, downloads its valid images (up to `jobs` at a
+/// time per page, with every network request also drawing a permit from
+/// the crawl's shared `request_limit`), and feeds
+/// any unvisited same-domain links it finds back into the frontier for the
+/// next depth.
+async fn crawl_page(
+	client: Arc<reqwest::Client>,
+	url: String,
+	depth: usize,
+	visited: Arc<Mutex<HashSet<String>>>,
+	save_path: Arc<PathBuf>,
+	frontier: tokio::sync::mpsc::UnboundedSender<(String, usize)>,
+	config: CrawlConfig,
+) -> Result<(), BoxError> {
+	use futures::stream::{self, StreamExt};
+
 	println!("\n[Depth {}] Crawling: {}", depth, url);
-	
-	let html = fetch_html(url)?;
-	let images = find_images(&html, url)?;
+
+	let html = fetch_html(&client, &url, config.retries, &config.request_limit).await?;
+	let images = find_images(&html, &url)?;
 	let valid_images: Vec<String> = images.into_iter()
 		.filter(|u| is_valid_image(u))
 		.collect();
-	
-	println!("Found {} images", valid_images.len());
-	for img_url in &valid_images {
-		if let Err(e) = download_image(img_url, save_path) {
-			eprintln!("Failed to download {}: {}", img_url, e);
-		}
-	}
-	
-	if depth < max_depth {
-		let links = find_links(&html, url)?;
+
+	println!("Found {} images on {}", valid_images.len(), url);
+	stream::iter(valid_images)
+		.for_each_concurrent(config.jobs, |img_url| {
+			let client = client.clone();
+			let save_path = save_path.clone();
+			let bounds = config.bounds;
+			let retries = config.retries;
+			let request_limit = config.request_limit.clone();
+			async move {
+				match save_image(&client, &img_url, &save_path, bounds, retries, &request_limit).await {
+					Ok(filepath) => println!("  {} -> {}", img_url, filepath.display()),
+					Err(e) => eprintln!("Failed to download {}: {}", img_url, e),
+				}
+			}
+		})
+		.await;
+
+	if depth < config.max_depth {
+		let links = find_links(&html, &url)?;
+		let mut visited = visited.lock().await;
 		for link in links {
-			crawl_recursive(&link, depth + 1, max_depth, visited, save_path)?;
+			if visited.insert(link.clone()) {
+				let _ = frontier.send((link, depth + 1));
+			}
 		}
 	}
-	
+
 	Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-	use std::collections::HashSet;
-	
+/// Crawls every seed URL using a bounded worker pool: a frontier channel
+/// of `(url, depth)` items is drained by a `FuturesUnordered` stream that
+/// keeps up to `jobs` page crawls in flight at once. The visited set is
+/// checked-and-inserted behind a mutex so concurrent tasks never fetch
+/// the same page twice.
+async fn crawl(
+	seeds: Vec<String>,
+	max_depth: usize,
+	save_path: PathBuf,
+	jobs: usize,
+	bounds: ResizeBounds,
+	retries: usize,
+) -> Result<usize, BoxError> {
+	use futures::stream::FuturesUnordered;
+	use futures::StreamExt;
+	use tokio::sync::mpsc;
+
+	let client = Arc::new(build_client()?);
+	let visited: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+	let save_path = Arc::new(save_path);
+	let (tx, mut rx) = mpsc::unbounded_channel::<(String, usize)>();
+	let request_limit = Arc::new(Semaphore::new(jobs));
+	let config = CrawlConfig { max_depth, jobs, bounds, retries, request_limit };
+
+	{
+		let mut visited = visited.lock().await;
+		for seed in seeds {
+			if visited.insert(seed.clone()) {
+				let _ = tx.send((seed, 0));
+			}
+		}
+	}
+
+	let mut in_flight = FuturesUnordered::new();
+
+	loop {
+		while in_flight.len() < jobs {
+			match rx.try_recv() {
+				Ok((url, depth)) => {
+					let client = client.clone();
+					let visited = visited.clone();
+					let save_path = save_path.clone();
+					let tx = tx.clone();
+					in_flight.push(tokio::spawn(crawl_page(
+						client, url, depth, visited, save_path, tx, config.clone(),
+					)));
+				}
+				Err(_) => break,
+			}
+		}
+
+		if in_flight.is_empty() {
+			break;
+		}
+
+		if let Some(Ok(Err(e))) = in_flight.next().await {
+			eprintln!("Crawl task failed: {}", e);
+		}
+	}
+
+	let visited_count = visited.lock().await.len();
+	Ok(visited_count)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
 	let args = Args::parse();
-	
+
+	let mut seeds = Vec::new();
+	if let Some(file) = &args.file {
+		seeds.extend(read_seed_urls(file)?);
+	}
+	if let Some(url) = &args.url {
+		seeds.push(url.clone());
+	}
+	if seeds.is_empty() {
+		return Err("no URLs to crawl: pass a URL or --file".into());
+	}
+
+	let bounds = ResizeBounds { max_width: args.max_width, max_height: args.max_height };
+
 	if args.recursive {
-		println!("Starting recursive crawl (max depth: {})", args.level);
-		let mut visited = HashSet::new();
-		crawl_recursive(&args.url, 0, args.level, &mut visited, &args.path)?;
-		println!("\nCrawl complete! Visited {} pages", visited.len());
+		println!("Starting recursive crawl of {} seed(s) (max depth: {}, jobs: {})", seeds.len(), args.level, args.jobs);
+		let visited_count = crawl(seeds, args.level, args.path.clone(), args.jobs, bounds, args.retries).await?;
+		println!("\nCrawl complete! Visited {} pages", visited_count);
 	} else {
-		println!("Fetching: {}", args.url);
-		let html = fetch_html(&args.url)?;
-		println!("Downloaded {} bytes", html.len());
-		
-		let images = find_images(&html, &args.url)?;
-		let valid_images: Vec<String> = images.into_iter()
-			.filter(|url| is_valid_image(url))
-			.collect();
-		
-		println!("Found {} valid images:", valid_images.len());
-		for img_url in &valid_images {
-			println!("  {}", img_url);
-			download_image(img_url, &args.path)?;
+		let client = build_client()?;
+		let request_limit = Semaphore::new(args.jobs);
+
+		let mut visited = HashSet::new();
+		for url in seeds {
+			if !visited.insert(url.clone()) {
+				continue;
+			}
+
+			println!("Fetching: {}", url);
+			let html = match fetch_html(&client, &url, args.retries, &request_limit).await {
+				Ok(html) => html,
+				Err(e) => {
+					eprintln!("Failed to fetch {}: {}", url, e);
+					continue;
+				}
+			};
+			println!("Downloaded {} bytes", html.len());
+
+			let images = match find_images(&html, &url) {
+				Ok(images) => images,
+				Err(e) => {
+					eprintln!("Failed to parse images from {}: {}", url, e);
+					continue;
+				}
+			};
+			let valid_images: Vec<String> = images.into_iter()
+				.filter(|url| is_valid_image(url))
+				.collect();
+
+			println!("Found {} valid images:", valid_images.len());
+			for img_url in &valid_images {
+				match save_image(&client, img_url, &args.path, bounds, args.retries, &request_limit).await {
+					Ok(filepath) => println!("  {} -> {}", img_url, filepath.display()),
+					Err(e) => eprintln!("Failed to download {}: {}", img_url, e),
+				}
+			}
 		}
 	}
 